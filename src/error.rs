@@ -0,0 +1,96 @@
+// このツール独自のエラー型。`run()` が返すエラーをケースごとに区別できるようにし、
+// テストでは（ローカライズされた）文字列の部分一致ではなく、種類や終了コードで検証できるようにします。
+
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum GoureiError {
+    /// 入力CSVファイルを開けなかった。
+    FileOpen { path: PathBuf, source: std::io::Error },
+    /// 入力CSVファイルのパースに失敗した。
+    CsvParse { path: PathBuf, source: csv::Error },
+    /// 入力CSVファイルに生徒が1人も含まれていなかった。
+    EmptyList { path: PathBuf },
+    /// `--count` で指定した人数に対して生徒が足りなかった。
+    TooFewStudents { found: usize, needed: usize },
+    /// `--roles` のラベル数が `--count` と一致しなかった。
+    RoleCountMismatch { roles: usize, count: usize },
+    /// `--delimiter` にASCII1文字以外が指定された。
+    InvalidDelimiter { delimiter: char },
+    /// 選出履歴ファイルへの書き込みに失敗した。
+    HistoryWrite { path: PathBuf, source: csv::Error },
+    /// 選択結果の出力（json/csv/tsv）の書き込みに失敗した。
+    OutputWrite(csv::Error),
+    /// 選択結果のJSONエンコードに失敗した。
+    OutputEncode(serde_json::Error),
+}
+
+impl fmt::Display for GoureiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoureiError::FileOpen { path, source } => {
+                write!(f, "Error: Could not open file '{}': {}", path.display(), source)
+            }
+            GoureiError::CsvParse { path, source } => {
+                write!(f, "Error: Failed to parse CSV file '{}': {}", path.display(), source)
+            }
+            GoureiError::EmptyList { path } => {
+                write!(f, "Error: The student list in '{}' is empty.", path.display())
+            }
+            GoureiError::TooFewStudents { found, needed } => write!(
+                f,
+                "Error: Not enough students to select {}. Found {}.",
+                needed, found
+            ),
+            GoureiError::RoleCountMismatch { roles, count } => write!(
+                f,
+                "Error: --roles has {} label(s) but --count is {}.",
+                roles, count
+            ),
+            GoureiError::InvalidDelimiter { delimiter } => write!(
+                f,
+                "Error: Could not convert '{}' delimiter to ASCII.",
+                delimiter
+            ),
+            GoureiError::HistoryWrite { path, source } => {
+                write!(f, "Error: Could not write history file '{}': {}", path.display(), source)
+            }
+            GoureiError::OutputWrite(source) => write!(f, "Error: Could not write output: {}", source),
+            GoureiError::OutputEncode(source) => write!(f, "Error: Could not encode output as JSON: {}", source),
+        }
+    }
+}
+
+impl std::error::Error for GoureiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GoureiError::FileOpen { source, .. } => Some(source),
+            GoureiError::CsvParse { source, .. } => Some(source),
+            GoureiError::HistoryWrite { source, .. } => Some(source),
+            GoureiError::OutputWrite(source) => Some(source),
+            GoureiError::OutputEncode(source) => Some(source),
+            GoureiError::EmptyList { .. }
+            | GoureiError::TooFewStudents { .. }
+            | GoureiError::RoleCountMismatch { .. }
+            | GoureiError::InvalidDelimiter { .. } => None,
+        }
+    }
+}
+
+impl GoureiError {
+    /// `process::exit` に渡す終了コード。エラーの種類ごとに値を分けておくことで、
+    /// 呼び出し側（シェルスクリプトなど）が失敗の原因を文字列解析せずに判定できます。
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GoureiError::FileOpen { .. } => 2,
+            GoureiError::CsvParse { .. } => 3,
+            GoureiError::EmptyList { .. } => 4,
+            GoureiError::TooFewStudents { .. } => 5,
+            GoureiError::RoleCountMismatch { .. } => 6,
+            GoureiError::HistoryWrite { .. } => 7,
+            GoureiError::OutputWrite(_) | GoureiError::OutputEncode(_) => 8,
+            GoureiError::InvalidDelimiter { .. } => 9,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+// `--history` で指定される選出履歴ファイルの読み書きと、
+// それを使った重み付きサンプリングをまとめたモジュール。
+
+use crate::Student;
+use csv::{ReaderBuilder, WriterBuilder};
+use rand::Rng;
+use std::collections::HashMap;
+use std::path::Path;
+
+// 生徒 id ごとの選出回数を読み込みます。ファイルが存在しない場合や
+// 壊れている（パースできない）場合は、エラーにせず全員カウント0として扱います。
+pub fn load_counts(path: &Path) -> HashMap<String, u64> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(_) => return HashMap::new(),
+    };
+
+    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+    let mut counts = HashMap::new();
+    for result in rdr.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        let id = record.get(0);
+        let count = record.get(1).and_then(|s| s.parse::<u64>().ok());
+        if let (Some(id), Some(count)) = (id, count) {
+            counts.insert(id.to_string(), count);
+        }
+    }
+    counts
+}
+
+// 選出回数を `id,count` のCSVとして書き出します。出力順を安定させるため id でソートします。
+pub fn save_counts(path: &Path, counts: &HashMap<String, u64>) -> Result<(), csv::Error> {
+    let mut wtr = WriterBuilder::new().has_headers(true).from_path(path)?;
+    wtr.write_record(["id", "count"])?;
+
+    let mut ids: Vec<&String> = counts.keys().collect();
+    ids.sort();
+    for id in ids {
+        wtr.write_record([id.as_str(), &counts[id].to_string()])?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+// Efraimidis–Spirakis の重み付きリザーバサンプリング（A-Res）で `count` 人を選びます。
+// 重み `w_i = 1 / (1 + 選出回数)` を用いるため、選出回数が少ない生徒ほど選ばれやすくなります。
+// 各生徒について一様乱数 `u_i` から鍵 `k_i = u_i^(1/w_i)` を計算し、鍵が大きい順に `count` 人を採用します。
+pub fn weighted_sample<'a, R: Rng + ?Sized>(
+    students: &'a [Student],
+    counts: &HashMap<String, u64>,
+    count: usize,
+    rng: &mut R,
+) -> Vec<&'a Student> {
+    let mut keyed: Vec<(f64, &Student)> = students
+        .iter()
+        .map(|student| {
+            let seen = counts.get(&student.id).copied().unwrap_or(0);
+            let weight = 1.0 / (1.0 + seen as f64);
+            let u: f64 = rng.gen_range(0.0..1.0);
+            let key = u.powf(1.0 / weight);
+            (key, student)
+        })
+        .collect();
+
+    keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    keyed.into_iter().take(count).map(|(_, student)| student).collect()
+}
@@ -1,19 +1,52 @@
 // `use` は他のモジュール（クレートやファイル）の機能を取り込む宣言です。
-use clap::Parser; // コマンドライン引数解析用クレート
-use csv::ReaderBuilder; // CSVファイル読み込み用クレート
+use clap::{Parser, Subcommand}; // コマンドライン引数解析用クレート
+use csv::{ReaderBuilder, WriterBuilder}; // CSVファイルの読み書き用クレート
 use rand::seq::SliceRandom; // スライスからランダムに要素を選ぶ機能
 use rand::SeedableRng; // 乱数生成器のシード設定用
-use std::error::Error; // 標準ライブラリのエラー処理用トレイト
+use std::collections::HashSet; // 重複idの検出用
 use std::fs::File; // ファイル操作用
 use std::path::PathBuf; // ファイルパス操作用
 use std::process; // プロセス制御用（終了コードなど）
 
+mod error; // このツール独自のエラー型 `GoureiError`
+mod history; // `--history` による重み付き抽選・履歴ファイルの読み書き
+
+use error::GoureiError;
+
+// `--roles` が指定されなかった場合に使う既定の役割ラベルの接頭辞。
+// `担当1`, `担当2`, … のように選出順の番号を付けて生成する。
+const DEFAULT_ROLE_PREFIX: &str = "担当";
+
 // `#[derive(...)]` は、指定されたトレイト（振る舞いの定義）を自動実装するマクロです。
 // `Debug` はデバッグ出力用、`Parser` は clap クレートがコマンドライン引数を解析するために必要です。
 #[derive(Parser, Debug)]
 // `#[clap(...)]` は clap クレート固有の属性マクロで、コマンドラインツールの情報を定義します。
 #[clap(author, version, about, long_about = None)]
-struct Args {
+struct Cli {
+    /// サブコマンド。省略した場合は `assign`（担当選出）として扱われます。
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// サブコマンド省略時 (= `assign`) に使う引数。
+    #[clap(flatten)]
+    assign: AssignArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 生徒を選出して役割ラベル付きで出力する（サブコマンド省略時と同じ動作）
+    Assign(AssignArgs),
+    /// 有効な生徒の人数を表示する
+    Count(RosterArgs),
+    /// 重複id・空欄nameなど、名簿の健全性を報告する
+    Stats(RosterArgs),
+    /// 役割ラベル無しでN件を一様にサンプリングする
+    Sample(SampleArgs),
+}
+
+// 名簿CSVの読み込みに関する共通引数。`assign` / `count` / `stats` / `sample` すべてで使う。
+#[derive(clap::Args, Debug)]
+struct RosterArgs {
     /// 使用する生徒リストCSVファイル (オプションなしで直接指定)
     #[clap(value_parser)] // 位置引数として設定
     input_file: Option<PathBuf>,
@@ -22,11 +55,81 @@ struct Args {
     #[clap(short, long, value_parser, help = "生徒リストCSVファイルへのパス (オプション)")] // help を追加して明確化
     file: Option<PathBuf>,
 
+    /// 入力CSVの区切り文字 (例: ',' ';' または タブ)
+    #[clap(long, value_parser, default_value_t = ',')]
+    delimiter: char,
+
+    /// 入力CSVにヘッダー行が無いことを指定する (1列目をid、2列目をnameとして扱う)
+    #[clap(long)]
+    no_headers: bool,
+
+    /// 行ごとに列数が異なることを許可する
+    #[clap(long)]
+    flexible: bool,
+}
+
+// `assign`（担当選出、デフォルト）の引数。
+#[derive(clap::Args, Debug)]
+struct AssignArgs {
+    #[clap(flatten)]
+    roster: RosterArgs,
+
+    /// 乱数生成器のシード（テスト用）
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+
+    /// 出力フォーマット (text, json, csv, tsv)
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// 選出する人数
+    #[clap(long, default_value_t = 2)]
+    count: usize,
+
+    /// 役割ラベルのカンマ区切りリスト (例: "正担当,副担当,記録")。
+    /// 指定する場合は `--count` と同じ個数のラベルが必要です。
+    #[clap(long, value_delimiter = ',')]
+    roles: Option<Vec<String>>,
+
+    /// 選出履歴ファイルのパス。指定すると選出回数に応じた重み付き抽選になり、
+    /// これまで選ばれた回数が少ない生徒ほど選ばれやすくなります。
+    #[clap(long, value_parser)]
+    history: Option<PathBuf>,
+}
+
+// `sample` の引数。
+#[derive(clap::Args, Debug)]
+struct SampleArgs {
+    #[clap(flatten)]
+    roster: RosterArgs,
+
+    /// サンプリングする件数
+    #[clap(long, default_value_t = 2)]
+    count: usize,
+
     /// 乱数生成器のシード（テスト用）
     #[clap(long, value_parser)]
     seed: Option<u64>,
 }
 
+// 出力フォーマットの種類。`clap::ValueEnum` により `--format json` のような文字列から
+// 自動で変換される。
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+    Tsv,
+}
+
+// 選択された生徒 1 名分の出力レコード。`Student` に役割ラベルを添えたもの。
+#[derive(Debug, serde::Serialize)]
+struct SelectionRecord {
+    role: String,
+    id: String,
+    name: String,
+}
+
 // `serde::Deserialize` は CSV からデータを構造体に変換（デシリアライズ）するために必要です。
 // `Clone` はデータを複製可能にするトレイトです。
 #[derive(Debug, serde::Deserialize, Clone)] // Cloneトレイトを実装
@@ -38,29 +141,39 @@ struct Student {
 }
 
 // `main` 関数はプログラムのエントリーポイント（開始地点）です。
-// `-> Result<(), Box<dyn Error>>` は、関数の戻り値の型を示します。
-// `Result` は成功（`Ok`）か失敗（`Err`）を表す型です。
-// `()` は成功時に値がないことを示します（Unit 型）。
-// `Box<dyn Error>` は、任意の型のエラーを保持できる型です（トレイトオブジェクト）。
-fn main() -> Result<(), Box<dyn Error>> {
+// エラーの種類ごとに終了コードを分けたいので、`Box<dyn Error>` ではなく
+// 自前の `GoureiError` を使い、`main` 側で `exit_code()` を見て終了します。
+fn main() {
     // `run()` 関数の結果を `if let` でパターンマッチングしています。
-    // `Err(e)` であれば、エラー `e` を標準エラー出力に出力し、プロセスを終了します。
+    // `Err(e)` であれば、エラー `e` を標準エラー出力に出力し、対応する終了コードでプロセスを終了します。
     if let Err(e) = run() {
         eprintln!("{}", e); // `eprintln!` は標準エラー出力へのマクロ
-        process::exit(1); // 終了コード 1 でプロセスを終了
+        process::exit(e.exit_code());
     }
-    // エラーがなければ `Ok(())` を返し、正常終了します。
-    Ok(())
 }
 
 // 実際の処理を行う関数。`main` と同じく `Result` を返します。
-fn run() -> Result<(), Box<dyn Error>> {
-    // `Args::parse()` は clap クレートの機能で、コマンドライン引数を解析して `Args` 構造体を生成します。
-    let args = Args::parse();
+fn run() -> Result<(), GoureiError> {
+    // `Cli::parse()` は clap クレートの機能で、コマンドライン引数を解析して `Cli` 構造体を生成します。
+    let cli = Cli::parse();
 
-    // ファイルパスの決定ロジックを修正
-    let file_path = args.input_file // まず位置引数を確認
-        .or(args.file) // 次に --file オプションを確認
+    // サブコマンドが指定されていなければ、従来どおり `assign`（担当選出）として扱います。
+    match cli.command {
+        Some(Command::Assign(assign)) => run_assign(&assign),
+        Some(Command::Count(roster)) => run_count(&roster),
+        Some(Command::Stats(roster)) => run_stats(&roster),
+        Some(Command::Sample(sample)) => run_sample(&sample),
+        None => run_assign(&cli.assign),
+    }
+}
+
+// 名簿CSVを読み込んで `Vec<Student>` にします。読み込みに使ったファイルの正規化済みパスも返します。
+fn load_students(roster: &RosterArgs) -> Result<(Vec<Student>, PathBuf), GoureiError> {
+    // ファイルパスの決定ロジック
+    let file_path = roster
+        .input_file
+        .clone() // まず位置引数を確認
+        .or_else(|| roster.file.clone()) // 次に --file オプションを確認
         .unwrap_or_else(|| PathBuf::from("./students.csv")); // どちらもなければデフォルト
 
     // `canonicalize()` はパスを絶対パスに正規化しようとします。
@@ -70,38 +183,71 @@ fn run() -> Result<(), Box<dyn Error>> {
     // --- CSVファイル読み込み ---
     // `File::open` は `Result<File, io::Error>` を返します。
     // `?` 演算子は `Result` が `Ok(value)` なら `value` を、`Err(e)` ならエラー `e` を早期リターンします。
-    // `.map_err(|e| ...)` は `Err` の場合にエラーの種類を変換します。ここでは詳細なエラーメッセージを生成しています。
-    let file = File::open(&canonical_path).map_err(|e| {
-         format!("Error: Could not open file '{}': {}", canonical_path.display(), e)
+    // `.map_err(|e| ...)` は `Err` の場合にエラーの種類を変換します。ここでは `GoureiError::FileOpen` に包みます。
+    let file = File::open(&canonical_path).map_err(|e| GoureiError::FileOpen {
+        path: canonical_path.clone(),
+        source: e,
     })?;
 
-    // `ReaderBuilder` で CSV リーダーの設定を行います。
+    // `--delimiter` はASCII1文字でなければCSVの1バイト区切り文字に変換できません。
+    // `as u8` は非ASCII文字を無言で下位バイトに切り詰めてしまうため、ここで明示的に検証します
+    // (xsv の `input` コマンドが同じ理由でASCII以外をエラーにするのに合わせています)。
+    if !roster.delimiter.is_ascii() {
+        return Err(GoureiError::InvalidDelimiter { delimiter: roster.delimiter });
+    }
+
+    // `ReaderBuilder` で CSV リーダーの設定を行います。区切り文字・ヘッダー有無・
+    // 列数の柔軟性は `--delimiter` / `--no-headers` / `--flexible` で上書きできます。
     let mut rdr = ReaderBuilder::new()
-        .has_headers(true) // ヘッダー行があると指定
-        .flexible(false) // 列数が固定であることを指定
+        .delimiter(roster.delimiter as u8)
+        .has_headers(!roster.no_headers)
+        .flexible(roster.flexible)
         .from_reader(file); // ファイルから読み込む
-    // `rdr.deserialize()` は CSV の各行を `Student` 構造体にデシリアライズするイテレータを返します。
-    // `.collect::<Result<_, _>>()` はイテレータの結果を `Vec<Student>` に集約します。
-    // `Result<Vec<Student>, csv::Error>` のような型になります。
-    // `_` は型推論に任せることを示します。
-    // ここでも `map_err` でエラーメッセージを整形し、`?` でエラー処理をしています。
-    let students: Vec<Student> = rdr.deserialize().collect::<Result<_, _>>().map_err(|e| {
-        format!("Error: Failed to parse CSV file '{}': {}", canonical_path.display(), e)
-    })?;
+
+    let students: Vec<Student> = if roster.no_headers {
+        // ヘッダーが無い場合は列名に頼れないため、1列目をid、2列目をnameとして
+        // 位置ベースで `Student` を組み立てます。
+        rdr.records()
+            .map(|result| {
+                result.map(|record| Student {
+                    id: record.get(0).unwrap_or_default().to_string(),
+                    name: record.get(1).unwrap_or_default().to_string(),
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map_err(|e| GoureiError::CsvParse { path: canonical_path.clone(), source: e })?
+    } else {
+        // `rdr.deserialize()` は CSV の各行を `Student` 構造体にデシリアライズするイテレータを返します。
+        // `.collect::<Result<_, _>>()` はイテレータの結果を `Vec<Student>` に集約します。
+        // `Result<Vec<Student>, csv::Error>` のような型になります。
+        // `_` は型推論に任せることを示します。
+        // ここでも `map_err` で `GoureiError::CsvParse` に変換し、`?` でエラー処理をしています。
+        rdr.deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|e| GoureiError::CsvParse { path: canonical_path.clone(), source: e })?
+    };
+
+    Ok((students, canonical_path))
+}
+
+// デフォルトの `assign` サブコマンド: 生徒を選出して役割ラベル付きで出力します。
+fn run_assign(args: &AssignArgs) -> Result<(), GoureiError> {
+    let (students, canonical_path) = load_students(&args.roster)?;
 
     // --- バリデーション ---
     // `students.is_empty()` でベクタが空かどうかをチェックします。
     if students.is_empty() {
-       // `Err(...)` でエラーを生成し、`.into()` で `Box<dyn Error>` 型に変換して早期リターンします。
-       return Err(format!("Error: The student list in '{}' is empty.", canonical_path.display()).into());
+        return Err(GoureiError::EmptyList { path: canonical_path });
     }
     // `students.len()` でベクタの要素数を取得します。
-    if students.len() < 2 {
-       return Err(format!(
-            "Error: Not enough students in '{}' to select two. Found {}.",
-            canonical_path.display(),
-            students.len()
-        ).into());
+    if students.len() < args.count {
+        return Err(GoureiError::TooFewStudents { found: students.len(), needed: args.count });
+    }
+    // `--roles` が指定されている場合は、ラベル数が `--count` と一致するか検証します。
+    if let Some(roles) = &args.roles {
+        if roles.len() != args.count {
+            return Err(GoureiError::RoleCountMismatch { roles: roles.len(), count: args.count });
+        }
     }
 
     // --- ランダム選択 ---
@@ -113,24 +259,132 @@ fn run() -> Result<(), Box<dyn Error>> {
         None => rand::rngs::StdRng::from_entropy(),
     };
 
-    // `students` ベクタ（実際にはそのスライス）から `rng` を使って重複なく 2 要素をランダムに選択します。
-    // `choose_multiple` は要素への参照（`&Student`）のベクタを返すイテレータを生成します。
-    // `collect::<Vec<_>>()` でそのイテレータの結果を `Vec<&Student>` に集約します。
-    let chosen_students_refs = students
-        .choose_multiple(&mut rng, 2) // `&mut rng` は可変の借用
-        .collect::<Vec<_>>();
+    // `--history` が指定されている場合は、これまでの選出回数を読み込み、
+    // 回数が少ない生徒ほど選ばれやすい重み付きサンプリングを行います。
+    // 指定がなければ従来どおりの一様なサンプリングです。
+    let mut history_counts = args.history.as_ref().map(|path| history::load_counts(path));
+
+    // `students` ベクタ（実際にはそのスライス）から `rng` を使って重複なく `count` 要素をランダムに選択します。
+    // `choose_multiple` / `history::weighted_sample` は要素への参照（`&Student`）のベクタを返します。
+    let chosen_students_refs: Vec<&Student> = match &history_counts {
+        Some(counts) => history::weighted_sample(&students, counts, args.count, &mut rng),
+        None => students
+            .choose_multiple(&mut rng, args.count) // `&mut rng` は可変の借用
+            .collect::<Vec<_>>(),
+    };
 
     // 選択された生徒の参照 (`&Student`) から、実際の `Student` データ をクローン（複製）して新しいベクタ `chosen_students` を作成します。
     // `.iter()` で参照のイテレータを取得し、`.map(|&s| s.clone())` で各参照 `&s` をデリファレンス（`*s`相当）して `clone()` し、
     // `.collect::<Vec<_>>()` で `Vec<Student>` に集約します。
     let chosen_students = chosen_students_refs.iter().map(|&s| s.clone()).collect::<Vec<_>>();
 
+    // 選出履歴ファイルが指定されている場合は、選ばれた生徒の回数を加算して書き戻します。
+    if let (Some(history_path), Some(counts)) = (&args.history, &mut history_counts) {
+        for student in &chosen_students {
+            *counts.entry(student.id.clone()).or_insert(0) += 1;
+        }
+        history::save_counts(history_path, counts)
+            .map_err(|e| GoureiError::HistoryWrite { path: history_path.clone(), source: e })?;
+    }
+
+    // 役割ラベルを解決します。`--roles` が指定されていればそれを使い、
+    // 無ければ `担当1`, `担当2`, … を選出順に割り当てます。
+    let role_labels = resolve_role_labels(args.count, &args.roles);
+
     // --- 出力 ---
-    // `println!` マクロで標準出力に整形された文字列を出力します。
-    // `{}` はプレースホルダーで、後の引数の値が挿入されます。
-    println!("正担当: {} {}", chosen_students[0].id, chosen_students[0].name);
-    println!("副担当: {} {}", chosen_students[1].id, chosen_students[1].name);
+    // フォーマットごとに出力方法を切り替えます。`text` は従来どおりの日本語表示、
+    // それ以外は他のプログラムへパイプしやすい構造化データを出力します。
+    match args.format {
+        OutputFormat::Text => {
+            // `println!` マクロで標準出力に整形された文字列を出力します。
+            // `{}` はプレースホルダーで、後の引数の値が挿入されます。
+            for (student, role) in chosen_students.iter().zip(role_labels.iter()) {
+                println!("{}: {} {}", role, student.id, student.name);
+            }
+        }
+        OutputFormat::Json => {
+            let records = selection_records(&chosen_students, &role_labels);
+            println!("{}", serde_json::to_string(&records).map_err(GoureiError::OutputEncode)?);
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let delimiter = if args.format == OutputFormat::Csv { b',' } else { b'\t' };
+            let mut wtr = WriterBuilder::new()
+                .delimiter(delimiter)
+                .from_writer(std::io::stdout());
+            for record in selection_records(&chosen_students, &role_labels) {
+                wtr.serialize(record).map_err(GoureiError::OutputWrite)?;
+            }
+            wtr.flush().map_err(|e| GoureiError::OutputWrite(e.into()))?;
+        }
+    }
 
     // すべて成功した場合、`Ok(())` を返します。
     Ok(())
 }
+
+// `count` サブコマンド: 読み込めた有効な生徒の人数だけを出力します。
+fn run_count(roster: &RosterArgs) -> Result<(), GoureiError> {
+    let (students, _canonical_path) = load_students(roster)?;
+    println!("{}", students.len());
+    Ok(())
+}
+
+// `stats` サブコマンド: 名簿の健全性（重複id・空欄name）を報告します。
+fn run_stats(roster: &RosterArgs) -> Result<(), GoureiError> {
+    let (students, _canonical_path) = load_students(roster)?;
+
+    let mut seen_ids = HashSet::new();
+    let mut duplicate_ids = HashSet::new();
+    for student in &students {
+        if !seen_ids.insert(student.id.clone()) {
+            duplicate_ids.insert(student.id.clone());
+        }
+    }
+    let blank_names = students.iter().filter(|s| s.name.trim().is_empty()).count();
+
+    println!("students: {}", students.len());
+    println!("duplicate_ids: {}", duplicate_ids.len());
+    println!("blank_names: {}", blank_names);
+    Ok(())
+}
+
+// `sample` サブコマンド: 役割ラベル無しで `--count` 件を一様にサンプリングして出力します。
+fn run_sample(args: &SampleArgs) -> Result<(), GoureiError> {
+    let (students, _canonical_path) = load_students(&args.roster)?;
+
+    if students.len() < args.count {
+        return Err(GoureiError::TooFewStudents { found: students.len(), needed: args.count });
+    }
+
+    let mut rng = match args.seed {
+        Some(seed) => rand::rngs::StdRng::seed_from_u64(seed),
+        None => rand::rngs::StdRng::from_entropy(),
+    };
+
+    for student in students.choose_multiple(&mut rng, args.count) {
+        println!("{} {}", student.id, student.name);
+    }
+    Ok(())
+}
+
+// 役割ラベルを解決します。`roles` が `Some` ならそのまま使い (長さは呼び出し側で検証済み)、
+// `None` なら `担当{n}` 形式のラベルを `count` 個生成します。
+fn resolve_role_labels(count: usize, roles: &Option<Vec<String>>) -> Vec<String> {
+    match roles {
+        Some(labels) => labels.clone(),
+        None => (1..=count).map(|n| format!("{}{}", DEFAULT_ROLE_PREFIX, n)).collect(),
+    }
+}
+
+// 選択された生徒を、役割ラベル付きの `SelectionRecord` のリストに変換します。
+fn selection_records(chosen_students: &[Student], role_labels: &[String]) -> Vec<SelectionRecord> {
+    chosen_students
+        .iter()
+        .zip(role_labels.iter())
+        .map(|(student, role)| SelectionRecord {
+            role: role.clone(),
+            id: student.id.clone(),
+            name: student.name.clone(),
+        })
+        .collect()
+}
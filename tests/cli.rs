@@ -27,7 +27,7 @@ fn test_fixed_seed_selection() {
        .arg("--seed")
        .arg("42"); // 固定シード
 
-    let expected_output = "正担当: 1 Alice\n副担当: 3 Charlie\n"; // <-- この行を調整
+    let expected_output = "担当1: 1 Alice\n担当2: 3 Charlie\n"; // <-- この行を調整
 
     cmd.assert().success().stdout(expected_output);
 }
@@ -43,8 +43,8 @@ fn test_cli_file_argument() {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains("正担当:"))
-        .stdout(predicate::str::contains("副担当:"))
+        .stdout(predicate::str::contains("担当1:"))
+        .stdout(predicate::str::contains("担当2:"))
         .stdout(predicate::str::is_match("^.*\n.*\n$").unwrap());
 }
 
@@ -59,10 +59,10 @@ fn test_default_file_path() {
 
     cmd.assert()
         .success()
-        .stdout(predicate::str::contains("正担当: 100 Grace").or(
-                predicate::str::contains("正担当: 200 Heidi")))
-        .stdout(predicate::str::contains("副担当: 100 Grace").or(
-                predicate::str::contains("副担当: 200 Heidi")));
+        .stdout(predicate::str::contains("担当1: 100 Grace").or(
+                predicate::str::contains("担当1: 200 Heidi")))
+        .stdout(predicate::str::contains("担当2: 100 Grace").or(
+                predicate::str::contains("担当2: 200 Heidi")));
 }
 
 #[test]
@@ -75,6 +75,7 @@ fn test_error_file_not_found() {
 
     cmd.assert()
         .failure()
+        .code(2)
         .stderr(predicate::str::contains("Error: Could not open file"));
 }
 
@@ -87,6 +88,7 @@ fn test_error_default_file_not_found() {
 
     cmd.assert()
         .failure()
+        .code(2)
         .stderr(predicate::str::contains("Error: Could not open file"));
 }
 
@@ -101,6 +103,7 @@ fn test_error_empty_file_header_only() {
 
     cmd.assert()
         .failure()
+        .code(4)
         .stderr(predicate::str::contains("Error: The student list"));
 }
 
@@ -115,6 +118,7 @@ fn test_error_empty_file_no_header() {
 
     cmd.assert()
         .failure()
+        .code(4)
         .stderr(predicate::str::contains("Error: The student list"));
 }
 
@@ -129,6 +133,7 @@ fn test_error_one_student() {
 
     cmd.assert()
         .failure()
+        .code(5)
         .stderr(predicate::str::contains("Error: Not enough students"));
 }
 
@@ -143,6 +148,7 @@ fn test_error_csv_format_invalid_delimiter() {
 
     cmd.assert()
         .failure()
+        .code(3)
         .stderr(predicate::str::contains("Error: Failed to parse CSV file"));
 }
 
@@ -157,9 +163,305 @@ fn test_error_csv_format_wrong_columns() {
 
     cmd.assert()
         .failure()
+        .code(3)
         .stderr(predicate::str::contains("Error: Failed to parse CSV file"));
 }
 
+#[test]
+fn test_format_json_output() {
+    let csv_content = "id,name\n1,Alice\n2,Bob\n3,Charlie\n4,David";
+    let (_dir, file_path) = setup_test_env("test_format_json.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--seed")
+        .arg("42")
+        .arg("--format")
+        .arg("json");
+
+    let expected_output = "[{\"role\":\"担当1\",\"id\":\"1\",\"name\":\"Alice\"},{\"role\":\"担当2\",\"id\":\"3\",\"name\":\"Charlie\"}]\n";
+
+    cmd.assert().success().stdout(expected_output);
+}
+
+#[test]
+fn test_format_csv_output() {
+    let csv_content = "id,name\n1,Alice\n2,Bob\n3,Charlie\n4,David";
+    let (_dir, file_path) = setup_test_env("test_format_csv.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--seed")
+        .arg("42")
+        .arg("--format")
+        .arg("csv");
+
+    let expected_output = "role,id,name\n担当1,1,Alice\n担当2,3,Charlie\n";
+
+    cmd.assert().success().stdout(expected_output);
+}
+
+#[test]
+fn test_custom_delimiter() {
+    let csv_content = "id;name\n1;Alice\n2;Bob"; // セミコロン区切り
+    let (_dir, file_path) = setup_test_env("custom_delimiter.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--delimiter")
+        .arg(";");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("担当1:"))
+        .stdout(predicate::str::contains("担当2:"));
+}
+
+#[test]
+fn test_error_non_ascii_delimiter() {
+    let csv_content = "id,name\n1,Alice\n2,Bob";
+    let (_dir, file_path) = setup_test_env("non_ascii_delimiter.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--delimiter")
+        .arg("\u{ff0c}"); // 全角カンマ (IME誤変換でありがちな非ASCII文字)
+
+    cmd.assert()
+        .failure()
+        .code(9)
+        .stderr(predicate::str::contains("Could not convert"))
+        .stderr(predicate::str::contains("delimiter to ASCII"));
+}
+
+#[test]
+fn test_no_headers_uses_positional_columns() {
+    let csv_content = "1,Alice\n2,Bob"; // ヘッダー行なし
+    let (_dir, file_path) = setup_test_env("no_headers.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--no-headers");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("担当1:"))
+        .stdout(predicate::str::contains("担当2:"));
+}
+
+#[test]
+fn test_flexible_allows_ragged_rows() {
+    let csv_content = "id,name,note\n1,Alice\n2,Bob,extra"; // 列数が不揃い
+    let (_dir, file_path) = setup_test_env("flexible.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--no-headers")
+        .arg("--flexible");
+
+    cmd.assert().success();
+}
+
+#[test]
+fn test_custom_count_and_roles() {
+    let csv_content = "id,name\n1,Alice\n2,Bob\n3,Charlie\n4,David";
+    let (_dir, file_path) = setup_test_env("custom_count_roles.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--seed")
+        .arg("42")
+        .arg("--count")
+        .arg("3")
+        .arg("--roles")
+        .arg("正担当,副担当,記録");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("正担当:"))
+        .stdout(predicate::str::contains("副担当:"))
+        .stdout(predicate::str::contains("記録:"));
+}
+
+#[test]
+fn test_count_falls_back_to_default_role_labels() {
+    let csv_content = "id,name\n1,Alice\n2,Bob\n3,Charlie";
+    let (_dir, file_path) = setup_test_env("default_role_labels.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--count")
+        .arg("3");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("担当1:"))
+        .stdout(predicate::str::contains("担当2:"))
+        .stdout(predicate::str::contains("担当3:"));
+}
+
+#[test]
+fn test_error_roles_count_mismatch() {
+    let csv_content = "id,name\n1,Alice\n2,Bob\n3,Charlie";
+    let (_dir, file_path) = setup_test_env("roles_mismatch.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--count")
+        .arg("2")
+        .arg("--roles")
+        .arg("正担当,副担当,記録");
+
+    cmd.assert()
+        .failure()
+        .code(6)
+        .stderr(predicate::str::contains("--roles has"));
+}
+
+#[test]
+fn test_error_not_enough_students_for_count() {
+    let csv_content = "id,name\n1,Alice\n2,Bob";
+    let (_dir, file_path) = setup_test_env("not_enough_for_count.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--count")
+        .arg("3");
+
+    cmd.assert()
+        .failure()
+        .code(5)
+        .stderr(predicate::str::contains("Error: Not enough students"));
+}
+
+#[test]
+fn test_history_prefers_unseen_students() {
+    let csv_content = "id,name\n1,Alice\n2,Bob\n3,Charlie";
+    let (dir, file_path) = setup_test_env("history_students.csv", csv_content);
+    let history_path = dir.path().join("history.csv");
+    fs::write(&history_path, "id,count\n1,5\n2,5\n3,0").expect("履歴ファイルの準備に失敗しました");
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--seed")
+        .arg("42")
+        .arg("--count")
+        .arg("1")
+        .arg("--history")
+        .arg(history_path.to_str().unwrap());
+
+    // 重みは 1, 2 が 1/6、未選出の 3 が 1 なので、3 (Charlie) が選ばれる確率は 3/4 と最も高い。
+    // シードを固定しているため、このテストでは毎回 3 (Charlie) が選ばれる。
+    cmd.assert().success().stdout(predicate::str::contains("3 Charlie"));
+}
+
+#[test]
+fn test_history_file_updated_after_run() {
+    let csv_content = "id,name\n1,Alice\n2,Bob";
+    let (dir, file_path) = setup_test_env("history_update.csv", csv_content);
+    let history_path = dir.path().join("history.csv");
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--history")
+        .arg(history_path.to_str().unwrap());
+
+    cmd.assert().success();
+
+    let history_content = fs::read_to_string(&history_path).expect("履歴ファイルの読み込みに失敗しました");
+    assert!(history_content.contains("id,count"));
+}
+
+#[test]
+fn test_history_missing_file_treated_as_zero_counts() {
+    let csv_content = "id,name\n1,Alice\n2,Bob";
+    let (dir, file_path) = setup_test_env("history_missing.csv", csv_content);
+    let history_path = dir.path().join("does_not_exist_yet.csv");
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--history")
+        .arg(history_path.to_str().unwrap());
+
+    cmd.assert().success();
+    assert!(history_path.exists());
+}
+
+#[test]
+fn test_count_subcommand() {
+    let csv_content = "id,name\n1,Alice\n2,Bob\n3,Charlie";
+    let (_dir, file_path) = setup_test_env("count_subcommand.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("count").arg("--file").arg(file_path.to_str().unwrap());
+
+    cmd.assert().success().stdout("3\n");
+}
+
+#[test]
+fn test_stats_subcommand_reports_duplicates_and_blanks() {
+    let csv_content = "id,name\n1,Alice\n1,Bob\n2,";
+    let (_dir, file_path) = setup_test_env("stats_subcommand.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("stats").arg("--file").arg(file_path.to_str().unwrap());
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("duplicate_ids: 1"))
+        .stdout(predicate::str::contains("blank_names: 1"));
+}
+
+#[test]
+fn test_sample_subcommand_has_no_role_labels() {
+    let csv_content = "id,name\n1,Alice\n2,Bob\n3,Charlie\n4,David";
+    let (_dir, file_path) = setup_test_env("sample_subcommand.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("sample")
+        .arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--seed")
+        .arg("42")
+        .arg("--count")
+        .arg("2");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match("^\\d+ \\S+\\n\\d+ \\S+\\n$").unwrap());
+}
+
+#[test]
+fn test_sample_subcommand_too_few_students() {
+    let csv_content = "id,name\n1,Alice";
+    let (_dir, file_path) = setup_test_env("sample_too_few.csv", csv_content);
+
+    let mut cmd = Command::cargo_bin("gourei_touban").unwrap();
+    cmd.arg("sample")
+        .arg("--file")
+        .arg(file_path.to_str().unwrap())
+        .arg("--count")
+        .arg("2");
+
+    cmd.assert()
+        .failure()
+        .code(5)
+        .stderr(predicate::str::contains("Error: Not enough students"));
+}
+
 #[test]
 fn test_positional_file_argument() {
     let csv_content = "id,name\npos1,PositionalArgUser1\npos2,PositionalArgUser2";
@@ -172,11 +474,11 @@ fn test_positional_file_argument() {
     cmd.assert()
         .success()
         .stdout(
-            predicate::str::contains("正担当: pos1 PositionalArgUser1")
-                .or(predicate::str::contains("正担当: pos2 PositionalArgUser2")),
+            predicate::str::contains("担当1: pos1 PositionalArgUser1")
+                .or(predicate::str::contains("担当1: pos2 PositionalArgUser2")),
         )
         .stdout(
-            predicate::str::contains("副担当: pos1 PositionalArgUser1")
-                .or(predicate::str::contains("副担当: pos2 PositionalArgUser2")),
+            predicate::str::contains("担当2: pos1 PositionalArgUser1")
+                .or(predicate::str::contains("担当2: pos2 PositionalArgUser2")),
         );
 }
\ No newline at end of file